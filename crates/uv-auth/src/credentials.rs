@@ -8,15 +8,49 @@ use reqwest::Request;
 use std::io::Read;
 use std::io::Write;
 use url::Url;
+use zeroize::Zeroizing;
+
+#[derive(Clone, PartialEq)]
+pub(crate) enum Credentials {
+    /// Credentials for HTTP Basic Authentication.
+    Basic {
+        /// The name of the user for authentication.
+        ///
+        /// Unlike `reqwest`, empty usernames should be encoded as `None` instead of an empty string.
+        username: Option<Zeroizing<String>>,
+        /// The password to use for authentication.
+        password: Option<Zeroizing<String>>,
+    },
+    /// Credentials for HTTP Bearer Authentication, i.e., a bearer token.
+    Bearer {
+        /// The bearer token to use for authentication.
+        token: Zeroizing<String>,
+    },
+}
 
-#[derive(Clone, Debug, PartialEq)]
-pub(crate) struct Credentials {
-    /// The name of the user for authentication.
-    ///
-    /// Unlike `reqwest`, empty usernames should be encoded as `None` instead of an empty string.
-    username: Option<String>,
-    /// The password to use for authentication.
-    password: Option<String>,
+/// A redacted stand-in for a secret value, so [`Credentials`]'s [`std::fmt::Debug`] impl never
+/// prints plaintext usernames, passwords, or tokens.
+struct Redacted;
+
+impl std::fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("\"***\"")
+    }
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Basic { username, password } => f
+                .debug_struct("Basic")
+                .field("username", &username.as_ref().map(|_| Redacted))
+                .field("password", &password.as_ref().map(|_| Redacted))
+                .finish(),
+            Self::Bearer { token: _ } => {
+                f.debug_struct("Bearer").field("token", &Redacted).finish()
+            }
+        }
+    }
 }
 
 impl Credentials {
@@ -27,19 +61,45 @@ impl Credentials {
                     .as_ref()
                     .is_some_and(|username| !username.is_empty())
         );
-        Self { username, password }
+        Self::Basic {
+            username: username.map(Zeroizing::new),
+            password: password.map(Zeroizing::new),
+        }
+    }
+
+    /// Create [`Credentials`] for HTTP Bearer Authentication from a token.
+    ///
+    /// Returns [`None`] if `token` contains bytes that cannot appear in an HTTP header value
+    /// (e.g. a newline), since such a token could never be sent as an
+    /// `Authorization: Bearer <token>` header.
+    pub fn bearer(token: String) -> Option<Self> {
+        if HeaderValue::from_bytes(token.as_bytes()).is_err() {
+            return None;
+        }
+        Some(Self::Bearer {
+            token: Zeroizing::new(token),
+        })
     }
 
     pub fn username(&self) -> Option<&str> {
-        self.username.as_deref()
+        match self {
+            Self::Basic { username, .. } => username.as_ref().map(|username| username.as_str()),
+            Self::Bearer { .. } => None,
+        }
     }
 
     pub fn password(&self) -> Option<&str> {
-        self.password.as_deref()
+        match self {
+            Self::Basic { password, .. } => password.as_ref().map(|password| password.as_str()),
+            Self::Bearer { .. } => None,
+        }
     }
 
     pub fn is_empty(&self) -> bool {
-        self.password.is_none() && self.username.is_none()
+        match self {
+            Self::Basic { username, password } => username.is_none() && password.is_none(),
+            Self::Bearer { token } => token.is_empty(),
+        }
     }
 
     /// Return [`Credentials`] for a [`Url`] from a [`Netrc`] file, if any.
@@ -57,9 +117,9 @@ impl Credentials {
             return None;
         };
 
-        Some(Credentials {
-            username: Some(entry.login.clone()),
-            password: Some(entry.password.clone()),
+        Some(Credentials::Basic {
+            username: Some(Zeroizing::new(entry.login.clone())),
+            password: Some(Zeroizing::new(entry.password.clone())),
         })
     }
 
@@ -70,29 +130,31 @@ impl Credentials {
         if url.username().is_empty() && url.password().is_none() {
             return None;
         }
-        Some(Self {
+        Some(Self::Basic {
             // Remove percent-encoding from URL credentials
             // See <https://github.com/pypa/pip/blob/06d21db4ff1ab69665c22a88718a4ea9757ca293/src/pip/_internal/utils/misc.py#L497-L499>
             username: if url.username().is_empty() {
                 None
             } else {
-                Some(
+                Some(Zeroizing::new(
                     urlencoding::decode(url.username())
                         .expect("An encoded username should always decode")
                         .into_owned(),
-                )
+                ))
             },
             password: url.password().map(|password| {
-                urlencoding::decode(password)
-                    .expect("An encoded password should always decode")
-                    .into_owned()
+                Zeroizing::new(
+                    urlencoding::decode(password)
+                        .expect("An encoded password should always decode")
+                        .into_owned(),
+                )
             }),
         })
     }
 
     /// Parse [`Credentials`] from an HTTP request, if any.
     ///
-    /// Only HTTP Basic Authentication is supported.
+    /// Supports both HTTP Basic and Bearer Authentication.
     pub fn from_request(request: &Request) -> Option<Self> {
         // First, attempt to retrieve the credentials from the URL
         Self::from_url(request.url()).or(
@@ -106,16 +168,21 @@ impl Credentials {
 
     /// Parse [`Credentials`] from an authorization header, if any.
     ///
-    /// Only HTTP Basic Authentication is supported.
-    /// [`None`] will be returned if another authoriziation scheme is detected.
+    /// Supports both HTTP Basic and Bearer Authentication.
+    /// [`None`] will be returned if another authoriziation scheme is detected, or if the
+    /// detected scheme's contents aren't conformant (e.g. a Bearer token that isn't UTF-8).
     ///
-    /// Panics if the authentication is not conformant to the HTTP Basic Authentication scheme:
-    /// - The contents must be base64 encoded
-    /// - There must be a `:` separator
+    /// Panics if the authentication is not conformant to the detected scheme:
+    /// - Basic: the contents must be base64 encoded and include a `:` separator
     pub(crate) fn from_header_value(header: &HeaderValue) -> Option<Self> {
+        if let Some(token) = header.as_bytes().strip_prefix(b"Bearer ") {
+            let token = std::str::from_utf8(token).ok()?;
+            return Self::bearer(token.to_string());
+        }
+
         let mut value = header.as_bytes().strip_prefix(b"Basic ")?;
         let mut decoder = DecoderReader::new(&mut value, &BASE64_STANDARD);
-        let mut buf = String::new();
+        let mut buf = Zeroizing::new(String::new());
         decoder
             .read_to_string(&mut buf)
             .expect("HTTP Basic Authentication should be base64 encoded.");
@@ -135,21 +202,31 @@ impl Credentials {
         Some(Self::new(username, password))
     }
 
-    /// Create an HTTP Basic Authentication header for the credentials.
+    /// Create an HTTP Authentication header for the credentials.
     ///
     /// Panics if the username or password cannot be base64 encoded.
     pub(crate) fn to_header_value(&self) -> HeaderValue {
-        // See: <https://github.com/seanmonstar/reqwest/blob/2c11ef000b151c2eebeed2c18a7b81042220c6b0/src/util.rs#L3>
-        let mut buf = b"Basic ".to_vec();
-        {
-            let mut encoder = EncoderWriter::new(&mut buf, &BASE64_STANDARD);
-            write!(encoder, "{}:", self.username().unwrap_or_default())
-                .expect("Write to base64 encoder should succeed");
-            if let Some(password) = self.password() {
-                write!(encoder, "{}", password).expect("Write to base64 encoder should succeed");
+        let mut header = match self {
+            Self::Basic { .. } => {
+                // See: <https://github.com/seanmonstar/reqwest/blob/2c11ef000b151c2eebeed2c18a7b81042220c6b0/src/util.rs#L3>
+                let mut buf = Zeroizing::new(b"Basic ".to_vec());
+                {
+                    let mut encoder = EncoderWriter::new(&mut *buf, &BASE64_STANDARD);
+                    write!(encoder, "{}:", self.username().unwrap_or_default())
+                        .expect("Write to base64 encoder should succeed");
+                    if let Some(password) = self.password() {
+                        write!(encoder, "{}", password)
+                            .expect("Write to base64 encoder should succeed");
+                    }
+                }
+                HeaderValue::from_bytes(&buf).expect("base64 is always valid HeaderValue")
             }
-        }
-        let mut header = HeaderValue::from_bytes(&buf).expect("base64 is always valid HeaderValue");
+            Self::Bearer { token } => {
+                // `Credentials::bearer` already rejects tokens that aren't valid header bytes.
+                HeaderValue::from_bytes(format!("Bearer {}", token.as_str()).as_bytes())
+                    .expect("bearer token was already validated by Credentials::bearer")
+            }
+        };
         header.set_sensitive(true);
         header
     }
@@ -274,4 +351,34 @@ mod test {
         assert_debug_snapshot!(header, @r###""Basic dXNlcjpwYXNzd29yZD09""###);
         assert_eq!(Credentials::from_header_value(&header), Some(credentials));
     }
+
+    #[test]
+    fn authenticated_request_from_bearer_token() {
+        let url = Url::parse("https://example.com/simple/first/").unwrap();
+        let credentials = Credentials::bearer("sometoken".to_string()).unwrap();
+
+        let mut request = reqwest::Request::new(reqwest::Method::GET, url);
+        request = credentials.authenticate(request);
+
+        let mut header = request
+            .headers()
+            .get(reqwest::header::AUTHORIZATION)
+            .expect("Authorization header should be set")
+            .clone();
+        header.set_sensitive(false);
+
+        assert_debug_snapshot!(header, @r###""Bearer sometoken""###);
+        assert_eq!(Credentials::from_header_value(&header), Some(credentials));
+    }
+
+    #[test]
+    fn bearer_rejects_invalid_header_bytes() {
+        assert!(Credentials::bearer("line\none\rtwo".to_string()).is_none());
+    }
+
+    #[test]
+    fn from_header_value_rejects_non_utf8_bearer_token() {
+        let header = HeaderValue::from_bytes(b"Bearer \xff").unwrap();
+        assert_eq!(Credentials::from_header_value(&header), None);
+    }
 }
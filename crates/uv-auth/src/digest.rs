@@ -0,0 +1,261 @@
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use md5::{Digest as _, Md5};
+use reqwest::header::HeaderValue;
+use reqwest::Method;
+
+use crate::credentials::Credentials;
+
+/// A parsed `WWW-Authenticate: Digest ...` challenge, per [RFC 2617](https://datatracker.ietf.org/doc/html/rfc2617#section-3.2.1).
+#[derive(Debug, Clone)]
+pub(crate) struct DigestChallenge {
+    pub(crate) realm: String,
+    pub(crate) nonce: String,
+    pub(crate) qop: Option<String>,
+    pub(crate) opaque: Option<String>,
+    pub(crate) algorithm: Option<String>,
+    /// The nonce count (`nc`), incremented for each request made against this challenge's nonce.
+    ///
+    /// Shared via `Arc` so that clones of a cached challenge (e.g. read back out of the
+    /// `CredentialStore`) still advance the *same* counter, rather than each clone restarting
+    /// from the count at the time it was cloned and producing a stale, repeated `nc`.
+    nc: Arc<AtomicU32>,
+}
+
+impl PartialEq for DigestChallenge {
+    fn eq(&self, other: &Self) -> bool {
+        self.realm == other.realm
+            && self.nonce == other.nonce
+            && self.qop == other.qop
+            && self.opaque == other.opaque
+            && self.algorithm == other.algorithm
+    }
+}
+
+impl DigestChallenge {
+    /// Parse a `WWW-Authenticate` header value into a [`DigestChallenge`].
+    ///
+    /// Returns [`None`] if the header does not use the `Digest` scheme, or is missing a
+    /// `realm` or `nonce` parameter.
+    pub(crate) fn parse(header: &HeaderValue) -> Option<Self> {
+        let value = header.to_str().ok()?;
+        // Auth-scheme tokens are case-insensitive, per RFC 7235 §2.1.
+        let (scheme, rest) = value.split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("Digest") {
+            return None;
+        }
+
+        let mut realm = None;
+        let mut nonce = None;
+        let mut qop = None;
+        let mut opaque = None;
+        let mut algorithm = None;
+
+        for (key, value) in parse_challenge_params(rest) {
+            match key {
+                "realm" => realm = Some(value.to_string()),
+                "nonce" => nonce = Some(value.to_string()),
+                "qop" => qop = Some(value.to_string()),
+                "opaque" => opaque = Some(value.to_string()),
+                "algorithm" => algorithm = Some(value.to_string()),
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            realm: realm?,
+            nonce: nonce?,
+            qop,
+            opaque,
+            algorithm,
+            nc: Arc::new(AtomicU32::new(0)),
+        })
+    }
+
+    /// Return the next nonce count (`nc`) for this challenge's nonce, as an 8-hex-digit string.
+    fn next_nc(&self) -> String {
+        let nc = self.nc.fetch_add(1, Ordering::SeqCst) + 1;
+        format!("{nc:08x}")
+    }
+}
+
+/// Parse the `key=value` parameters of a `WWW-Authenticate` challenge (the part after the scheme
+/// token) into `(key, value)` pairs, splitting on commas that are not inside a quoted value and
+/// stripping the surrounding quotes, if any, from each value.
+///
+/// Shared by [`DigestChallenge::parse`] and the retry middleware's `realm` lookup, since both
+/// need to parse the same comma-separated, possibly-quoted grammar and a comma inside a quoted
+/// value (e.g. `error_description="too many, retry later"`) must not be treated as a separator.
+pub(crate) fn parse_challenge_params(s: &str) -> impl Iterator<Item = (&str, &str)> {
+    split_on_unquoted_commas(s).filter_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        Some((key.trim(), value.trim().trim_matches('"')))
+    })
+}
+
+/// Split a string on commas that are not inside a quoted (`"..."`) span.
+fn split_on_unquoted_commas(s: &str) -> impl Iterator<Item = &str> {
+    let mut params = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                params.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    params.push(s[start..].trim());
+    params.into_iter().filter(|param| !param.is_empty())
+}
+
+/// Escape a value for inclusion in a quoted Digest header parameter.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// `MD5(a:b:c)`, hex-encoded, per the `H(data)` notation in RFC 2617.
+fn md5_hex(parts: &[&str]) -> String {
+    let mut hasher = Md5::new();
+    hasher.update(parts.join(":").as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Generate a fresh client nonce (`cnonce`) for a Digest request.
+fn generate_cnonce() -> String {
+    let bytes: [u8; 8] = rand::random();
+    bytes.iter().fold(String::new(), |mut acc, byte| {
+        let _ = write!(acc, "{byte:02x}");
+        acc
+    })
+}
+
+impl Credentials {
+    /// Create an HTTP Digest Authentication header value for the credentials, per
+    /// [RFC 2617](https://datatracker.ietf.org/doc/html/rfc2617#section-3.2.2), in response to
+    /// the server's `challenge` for a request to `uri` using `method`.
+    pub(crate) fn to_digest_header_value(
+        &self,
+        challenge: &DigestChallenge,
+        method: &Method,
+        uri: &str,
+    ) -> HeaderValue {
+        let username = self.username().unwrap_or_default();
+        let password = self.password().unwrap_or_default();
+
+        let ha1 = md5_hex(&[username, &challenge.realm, password]);
+        let ha2 = md5_hex(&[method.as_str(), uri]);
+
+        let mut value = format!(
+            r#"Digest username="{}", realm="{}", nonce="{}", uri="{}""#,
+            escape(username),
+            escape(&challenge.realm),
+            escape(&challenge.nonce),
+            escape(uri),
+        );
+
+        let response = if challenge.qop.as_deref() == Some("auth") {
+            let nc = challenge.next_nc();
+            let cnonce = generate_cnonce();
+            let response = md5_hex(&[&ha1, &challenge.nonce, &nc, &cnonce, "auth", &ha2]);
+            let _ = write!(
+                value,
+                r#", qop=auth, nc={nc}, cnonce="{}""#,
+                escape(&cnonce)
+            );
+            response
+        } else {
+            md5_hex(&[&ha1, &challenge.nonce, &ha2])
+        };
+        let _ = write!(value, r#", response="{}""#, escape(&response));
+
+        if let Some(opaque) = &challenge.opaque {
+            let _ = write!(value, r#", opaque="{}""#, escape(opaque));
+        }
+
+        let mut header = HeaderValue::from_bytes(value.as_bytes())
+            .expect("Digest header should be a valid HeaderValue");
+        header.set_sensitive(true);
+        header
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_challenge() {
+        let header = HeaderValue::from_static(
+            r#"Digest realm="testrealm@host.com", qop="auth", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093", opaque="5ccc069c403ebaf9f0171e9517f40e41""#,
+        );
+        let challenge = DigestChallenge::parse(&header).unwrap();
+        assert_eq!(challenge.realm, "testrealm@host.com");
+        assert_eq!(challenge.nonce, "dcd98b7102dd2f0e8b11d0f600bfb0c093");
+        assert_eq!(challenge.qop.as_deref(), Some("auth"));
+        assert_eq!(
+            challenge.opaque.as_deref(),
+            Some("5ccc069c403ebaf9f0171e9517f40e41")
+        );
+    }
+
+    #[test]
+    fn parse_challenge_with_comma_inside_quoted_value() {
+        let header = HeaderValue::from_static(
+            r#"Digest realm="testrealm@host.com", error_description="too many, retry later", nonce="abc123""#,
+        );
+        let challenge = DigestChallenge::parse(&header).unwrap();
+        assert_eq!(challenge.realm, "testrealm@host.com");
+        assert_eq!(challenge.nonce, "abc123");
+    }
+
+    #[test]
+    fn parse_challenge_wrong_scheme() {
+        let header = HeaderValue::from_static(r#"Basic realm="testrealm@host.com""#);
+        assert!(DigestChallenge::parse(&header).is_none());
+    }
+
+    #[test]
+    fn digest_response_without_qop() {
+        // Values taken from the worked example in RFC 2617 §3.5, minus qop.
+        let challenge = DigestChallenge {
+            realm: "testrealm@host.com".to_string(),
+            nonce: "dcd98b7102dd2f0e8b11d0f600bfb0c093".to_string(),
+            qop: None,
+            opaque: Some("5ccc069c403ebaf9f0171e9517f40e41".to_string()),
+            algorithm: None,
+            nc: Arc::new(AtomicU32::new(0)),
+        };
+        let credentials = Credentials::new(
+            Some("Mufasa".to_string()),
+            Some("Circle Of Life".to_string()),
+        );
+        let header =
+            credentials.to_digest_header_value(&challenge, &Method::GET, "/dir/index.html");
+        let value = header.to_str().unwrap();
+        assert!(value.contains(r#"username="Mufasa""#));
+        assert!(value.contains(r#"uri="/dir/index.html""#));
+        assert!(!value.contains("qop="));
+    }
+
+    #[test]
+    fn nc_advances_across_clones() {
+        // A clone of a challenge (e.g. read back out of the credential store) must share the
+        // same nonce counter as the original, or repeated requests against the same nonce will
+        // all send the same `nc` instead of incrementing it.
+        let header = HeaderValue::from_static(
+            r#"Digest realm="testrealm@host.com", qop="auth", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093""#,
+        );
+        let challenge = DigestChallenge::parse(&header).unwrap();
+        let cloned = challenge.clone();
+
+        assert_eq!(challenge.next_nc(), "00000001");
+        assert_eq!(cloned.next_nc(), "00000002");
+        assert_eq!(challenge.next_nc(), "00000003");
+    }
+}
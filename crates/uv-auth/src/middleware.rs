@@ -0,0 +1,431 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use http::Extensions;
+use netrc::Netrc;
+use reqwest::{Request, Response, StatusCode};
+use reqwest_middleware::{Middleware, Next};
+use url::{Position, Url};
+
+use crate::credentials::Credentials;
+use crate::digest::{parse_challenge_params, DigestChallenge};
+use crate::store::{CredentialStore, StoredCredentials};
+
+/// Whether `value` starts with `prefix`, ignoring ASCII case.
+fn starts_with_ignore_ascii_case(value: &str, prefix: &str) -> bool {
+    value
+        .get(..prefix.len())
+        .is_some_and(|head| head.eq_ignore_ascii_case(prefix))
+}
+
+/// The Request-URI to use in a Digest `uri` parameter: the path and, if present, the query
+/// string, per [RFC 2617 §3.2.2](https://datatracker.ietf.org/doc/html/rfc2617#section-3.2.2).
+fn digest_uri(url: &Url) -> &str {
+    &url[Position::BeforePath..Position::AfterQuery]
+}
+
+/// The scheme a server asked for in a `WWW-Authenticate` challenge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scheme {
+    Basic,
+    Bearer,
+    Digest,
+}
+
+impl Scheme {
+    /// Determine the [`Scheme`] a `WWW-Authenticate` header is challenging for.
+    ///
+    /// Auth-scheme tokens are matched case-insensitively, per [RFC 7235 §2.1](https://datatracker.ietf.org/doc/html/rfc7235#section-2.1).
+    fn from_header(header: &http::HeaderValue) -> Option<Self> {
+        let value = header.to_str().ok()?;
+        if starts_with_ignore_ascii_case(value, "Basic") {
+            Some(Self::Basic)
+        } else if starts_with_ignore_ascii_case(value, "Bearer") {
+            Some(Self::Bearer)
+        } else if starts_with_ignore_ascii_case(value, "Digest") {
+            Some(Self::Digest)
+        } else {
+            None
+        }
+    }
+
+    /// Extract the `realm` parameter from a `WWW-Authenticate` header, if present.
+    ///
+    /// Reuses [`parse_challenge_params`], the same quote-aware parser `DigestChallenge` uses, so a
+    /// comma inside a quoted parameter (e.g. `error_description="too many, retry later"`) isn't
+    /// mistaken for a parameter separator here but not there.
+    fn realm(header: &http::HeaderValue) -> Option<String> {
+        let value = header.to_str().ok()?;
+        let params = value.split_once(' ').map_or("", |(_, params)| params);
+        parse_challenge_params(params)
+            .find(|(key, _)| *key == "realm")
+            .map(|(_, value)| value.to_string())
+    }
+}
+
+/// A `reqwest` middleware that authenticates requests in response to a `401` challenge.
+///
+/// Requests are sent unauthenticated (or pre-emptively authenticated from the [`CredentialStore`])
+/// first. If the server responds with `401 Unauthorized` and a `WWW-Authenticate` header, the
+/// middleware resolves [`Credentials`] for the request's URL (checking the URL itself, then the
+/// store, then a `.netrc` file, in that order), attaches them (as a Digest response to a Digest
+/// challenge, otherwise via [`Credentials::authenticate`] in the credentials' own Basic or Bearer
+/// scheme), and retries the request once. Credentials realized this way are recorded in the store,
+/// keyed by the responding origin and realm, so later requests to the same host are authenticated
+/// pre-emptively, without incurring another round trip.
+pub(crate) struct AuthMiddleware {
+    netrc: Option<Netrc>,
+    store: CredentialStore,
+}
+
+impl AuthMiddleware {
+    /// Create an [`AuthMiddleware`], optionally backed by a `.netrc` file.
+    pub(crate) fn new(netrc: Option<Netrc>) -> Self {
+        Self {
+            netrc,
+            store: CredentialStore::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Middleware for AuthMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let url = req.url().clone();
+
+        if let Some(stored) = self.store.get(&url) {
+            let method = req.method().clone();
+            let uri = digest_uri(req.url()).to_string();
+            req = stored.authenticate(req, &method, &uri);
+        }
+
+        // Keep a copy of the unauthenticated (or pre-emptively authenticated) request in case we
+        // need to retry it with different credentials below.
+        let retry_req = req.try_clone();
+
+        let response = next.clone().run(req, extensions).await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+
+        // The response may have been reached via a redirect; refuse to reuse credentials realized
+        // for the original URL against a different origin (scheme, host, or port).
+        if !CredentialStore::redirect_is_safe(&url, response.url()) {
+            return Ok(response);
+        }
+
+        let Some(retry_req) = retry_req else {
+            // The request body can't be replayed (e.g., a stream), so we can't retry it.
+            return Ok(response);
+        };
+
+        let Some(challenge) = response.headers().get(reqwest::header::WWW_AUTHENTICATE) else {
+            return Ok(response);
+        };
+        let Some(scheme) = Scheme::from_header(challenge) else {
+            return Ok(response);
+        };
+        let realm = Scheme::realm(challenge);
+
+        // Resolve credentials for the retry, in priority order: the URL itself, then the store
+        // (e.g. a realm recorded from an earlier challenge to this origin), then `.netrc`.
+        let Some(credentials) = self
+            .store
+            .get_or_fallback(&url, self.netrc.as_ref())
+            .map(|stored| stored.credentials().clone())
+        else {
+            return Ok(response);
+        };
+
+        let (stored, retry_req) = match scheme {
+            Scheme::Digest => {
+                let Some(challenge) = DigestChallenge::parse(challenge) else {
+                    return Ok(response);
+                };
+                let mut retry_req = retry_req;
+                let uri = digest_uri(retry_req.url()).to_string();
+                let header =
+                    credentials.to_digest_header_value(&challenge, retry_req.method(), &uri);
+                retry_req
+                    .headers_mut()
+                    .insert(reqwest::header::AUTHORIZATION, header);
+                (
+                    StoredCredentials::Digest {
+                        credentials,
+                        challenge,
+                    },
+                    retry_req,
+                )
+            }
+            Scheme::Basic | Scheme::Bearer => {
+                let retry_req = credentials.authenticate(retry_req);
+                (StoredCredentials::Direct(credentials), retry_req)
+            }
+        };
+
+        self.store.insert(response.url(), realm.as_deref(), stored);
+
+        next.run(retry_req, extensions)
+            .await
+            .context("Failed to retry authenticated request")
+            .map_err(reqwest_middleware::Error::Middleware)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use reqwest_middleware::ClientBuilder;
+    use url::Url;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, Request as MockRequest, ResponseTemplate};
+
+    fn client(netrc: Option<Netrc>) -> reqwest_middleware::ClientWithMiddleware {
+        ClientBuilder::new(reqwest::Client::new())
+            .with(AuthMiddleware::new(netrc))
+            .build()
+    }
+
+    /// A `GET` request to `url`, built directly rather than through `ClientWithMiddleware::get`:
+    /// reqwest's request builder moves credentials embedded in the URL into an `Authorization`
+    /// header before any middleware runs, which would hide them from the middleware.
+    fn get(url: Url) -> Request {
+        Request::new(reqwest::Method::GET, url)
+    }
+
+    /// Responds `200` if the request carries an `Authorization` header, `401` with the given
+    /// `WWW-Authenticate` challenge otherwise.
+    fn challenge_until_authenticated(
+        www_authenticate: &'static str,
+    ) -> impl Fn(&MockRequest) -> ResponseTemplate {
+        move |req: &MockRequest| {
+            if req.headers.contains_key("authorization") {
+                ResponseTemplate::new(200)
+            } else {
+                ResponseTemplate::new(401).insert_header("www-authenticate", www_authenticate)
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_with_basic_credentials_from_url() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/simple/"))
+            .respond_with(challenge_until_authenticated(r#"Basic realm="test""#))
+            .mount(&server)
+            .await;
+
+        let mut url = Url::parse(&server.uri()).unwrap();
+        url.set_username("user").unwrap();
+        url.set_password(Some("password")).unwrap();
+        let url = url.join("/simple/").unwrap();
+
+        let response = client(None).execute(get(url)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn returns_401_for_bearer_challenge_without_credentials() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/simple/"))
+            .respond_with(challenge_until_authenticated(r#"Bearer realm="test""#))
+            .mount(&server)
+            .await;
+
+        // No credentials are available (none in the URL, no `.netrc`, nothing stored), so the
+        // middleware has nothing to retry with and returns the 401 as-is rather than looping.
+        let url = Url::parse(&server.uri()).unwrap().join("/simple/").unwrap();
+        let response = client(None).get(url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn retries_with_digest_credentials_from_url() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/simple/"))
+            .respond_with(challenge_until_authenticated(
+                r#"Digest realm="test", qop="auth", nonce="abc123""#,
+            ))
+            .mount(&server)
+            .await;
+
+        let mut url = Url::parse(&server.uri()).unwrap();
+        url.set_username("user").unwrap();
+        url.set_password(Some("password")).unwrap();
+        let url = url.join("/simple/").unwrap();
+
+        let response = client(None).execute(get(url)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn preemptively_authenticates_after_first_challenge() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/simple/"))
+            .respond_with(challenge_until_authenticated(r#"Basic realm="test""#))
+            .mount(&server)
+            .await;
+
+        let mut url = Url::parse(&server.uri()).unwrap();
+        url.set_username("user").unwrap();
+        url.set_password(Some("password")).unwrap();
+        let url = url.join("/simple/").unwrap();
+
+        let client = client(None);
+        assert_eq!(
+            client.execute(get(url.clone())).await.unwrap().status(),
+            StatusCode::OK
+        );
+
+        // The second request should be authenticated on the first attempt, from the store.
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2, "first request should 401 then retry");
+
+        assert_eq!(
+            client.execute(get(url)).await.unwrap().status(),
+            StatusCode::OK
+        );
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(
+            requests.len(),
+            3,
+            "second request should be pre-emptively authenticated, no retry"
+        );
+    }
+
+    #[tokio::test]
+    async fn preemptive_digest_requests_advance_the_nonce_count() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/simple/"))
+            .respond_with(challenge_until_authenticated(
+                r#"Digest realm="test", qop="auth", nonce="abc123""#,
+            ))
+            .mount(&server)
+            .await;
+
+        let mut url = Url::parse(&server.uri()).unwrap();
+        url.set_username("user").unwrap();
+        url.set_password(Some("password")).unwrap();
+        let url = url.join("/simple/").unwrap();
+
+        let client = client(None);
+        assert_eq!(
+            client.execute(get(url.clone())).await.unwrap().status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            client.execute(get(url.clone())).await.unwrap().status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            client.execute(get(url)).await.unwrap().status(),
+            StatusCode::OK
+        );
+
+        // The first request 401s then retries (nc=00000001); the second and third are
+        // pre-emptively authenticated from the store and must each advance the shared nonce
+        // count, not repeat it.
+        let requests = server.received_requests().await.unwrap();
+        let nc_values: Vec<_> = requests
+            .iter()
+            .filter_map(|req| req.headers.get("authorization"))
+            .map(|header| {
+                let value = header.to_str().unwrap();
+                let (_, nc) = value
+                    .split_once("nc=")
+                    .expect("authenticated requests should include nc");
+                nc[..8].to_string()
+            })
+            .collect();
+        assert_eq!(nc_values, vec!["00000001", "00000002", "00000003"]);
+    }
+
+    #[tokio::test]
+    async fn does_not_replay_credentials_across_a_redirect_to_a_different_origin() {
+        let challenged = MockServer::start().await;
+        let redirect_target = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/next"))
+            .respond_with(
+                ResponseTemplate::new(401)
+                    .insert_header("www-authenticate", r#"Basic realm="test""#),
+            )
+            .mount(&redirect_target)
+            .await;
+
+        let location = format!("{}/next", redirect_target.uri());
+        Mock::given(method("GET"))
+            .and(path("/start"))
+            .respond_with(ResponseTemplate::new(302).insert_header("location", location.as_str()))
+            .mount(&challenged)
+            .await;
+
+        let url = Url::parse(&challenged.uri())
+            .unwrap()
+            .join("/start")
+            .unwrap();
+
+        // No credentials are available for either origin, so this only confirms that a 401 reached
+        // through a cross-origin redirect is returned as-is (no loop or panic). Both mock servers
+        // listen on the same host, so the two origins differ by port.
+        let response = client(None).get(url.clone()).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_ne!(response.url().port(), url.port());
+    }
+
+    #[test]
+    fn scheme_from_header() {
+        assert_eq!(
+            Scheme::from_header(&http::HeaderValue::from_static(r#"Basic realm="test""#)),
+            Some(Scheme::Basic)
+        );
+        assert_eq!(
+            Scheme::from_header(&http::HeaderValue::from_static("Bearer")),
+            Some(Scheme::Bearer)
+        );
+        assert_eq!(
+            Scheme::from_header(&http::HeaderValue::from_static(r#"Digest realm="test""#)),
+            Some(Scheme::Digest)
+        );
+        assert_eq!(
+            Scheme::from_header(&http::HeaderValue::from_static("Negotiate")),
+            None
+        );
+    }
+
+    #[test]
+    fn digest_uri_includes_query_string() {
+        let url = Url::parse("https://example.com/simple/?cursor=abc").unwrap();
+        assert_eq!(digest_uri(&url), "/simple/?cursor=abc");
+    }
+
+    #[test]
+    fn scheme_from_header_is_case_insensitive() {
+        assert_eq!(
+            Scheme::from_header(&http::HeaderValue::from_static(r#"digest realm="test""#)),
+            Some(Scheme::Digest)
+        );
+        assert_eq!(
+            Scheme::from_header(&http::HeaderValue::from_static(r#"BASIC realm="test""#)),
+            Some(Scheme::Basic)
+        );
+    }
+
+    #[test]
+    fn realm_handles_comma_inside_quoted_value() {
+        let header = http::HeaderValue::from_static(
+            r#"Digest realm="test-realm", error_description="too many, retry later""#,
+        );
+        assert_eq!(Scheme::realm(&header).as_deref(), Some("test-realm"));
+    }
+}
@@ -0,0 +1,260 @@
+use dashmap::DashMap;
+use netrc::Netrc;
+use reqwest::{Method, Request};
+use url::Url;
+
+use crate::credentials::Credentials;
+use crate::digest::DigestChallenge;
+
+/// A request's authentication scope: its origin (scheme, host, and port), and optionally the
+/// realm of the `WWW-Authenticate` challenge that prompted authentication.
+///
+/// Scoping by realm, in addition to origin, keeps credentials for one protected area of a host
+/// from leaking into another protected area of the same host that uses a different realm.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Scope {
+    scheme: String,
+    host: String,
+    port: Option<u16>,
+    realm: Option<String>,
+}
+
+impl Scope {
+    /// The origin of a request to `url`, without a realm.
+    fn from_url(url: &Url) -> Option<Self> {
+        Some(Self {
+            scheme: url.scheme().to_string(),
+            host: url.host_str()?.to_string(),
+            port: url.port_or_known_default(),
+            realm: None,
+        })
+    }
+
+    /// The scope of a request to `url`, narrowed to a realm from a challenge.
+    fn from_url_and_realm(url: &Url, realm: &str) -> Option<Self> {
+        Some(Self {
+            realm: Some(realm.to_string()),
+            ..Self::from_url(url)?
+        })
+    }
+
+    /// Whether `self` and `other` share an origin (scheme, host, and port), ignoring realm.
+    fn same_origin(&self, other: &Self) -> bool {
+        self.scheme == other.scheme && self.host == other.host && self.port == other.port
+    }
+}
+
+/// [`Credentials`] realized for a scope, along with the scheme they should be attached with.
+///
+/// A [`Scheme::Digest`](crate::digest) challenge can't be answered by [`Credentials::authenticate`]
+/// alone (the response hash depends on the challenge and the request's method and URI), so the
+/// store keeps the [`DigestChallenge`] alongside the credentials it was solved with, and uses it
+/// to re-derive a fresh Digest header for later requests to the same scope.
+#[derive(Debug, Clone)]
+pub(crate) enum StoredCredentials {
+    /// Attach via the scheme the credentials themselves encode (Basic or Bearer).
+    Direct(Credentials),
+    /// Attach via HTTP Digest, re-deriving the response from the challenge realized earlier.
+    Digest {
+        credentials: Credentials,
+        challenge: DigestChallenge,
+    },
+}
+
+impl StoredCredentials {
+    /// The underlying [`Credentials`], regardless of which scheme they're attached with.
+    pub(crate) fn credentials(&self) -> &Credentials {
+        match self {
+            Self::Direct(credentials) | Self::Digest { credentials, .. } => credentials,
+        }
+    }
+
+    /// Attach these credentials to `request`, using `method` and `uri` if a Digest response needs
+    /// to be (re-)computed.
+    pub(crate) fn authenticate(&self, request: Request, method: &Method, uri: &str) -> Request {
+        match self {
+            Self::Direct(credentials) => credentials.authenticate(request),
+            Self::Digest {
+                credentials,
+                challenge,
+            } => {
+                let header = credentials.to_digest_header_value(challenge, method, uri);
+                let mut request = request;
+                request
+                    .headers_mut()
+                    .insert(reqwest::header::AUTHORIZATION, header);
+                request
+            }
+        }
+    }
+}
+
+/// A per-host, per-realm store of [`StoredCredentials`].
+///
+/// Keying credentials by scope (rather than caching a single set globally) ensures uv doesn't
+/// send one index's token or password to another host, or to an unrelated protected area of the
+/// same host, on redirect.
+#[derive(Debug, Default)]
+pub(crate) struct CredentialStore {
+    credentials: DashMap<Scope, StoredCredentials>,
+}
+
+impl CredentialStore {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve [`StoredCredentials`] for a request to `url`.
+    ///
+    /// Checks, in priority order: credentials embedded in the URL itself, previously-stored
+    /// credentials for the URL's origin, then a `.netrc` file.
+    pub(crate) fn get_or_fallback(
+        &self,
+        url: &Url,
+        netrc: Option<&Netrc>,
+    ) -> Option<StoredCredentials> {
+        Credentials::from_url(url)
+            .map(StoredCredentials::Direct)
+            .or_else(|| self.get(url))
+            .or_else(|| {
+                netrc
+                    .and_then(|netrc| Credentials::from_netrc(netrc, url, None))
+                    .map(StoredCredentials::Direct)
+            })
+    }
+
+    /// Look up previously-stored [`StoredCredentials`] for `url`'s origin, if any.
+    ///
+    /// An origin-wide entry (recorded from a challenge without a realm) is unambiguous and is
+    /// always preferred. Otherwise, since the realm a not-yet-sent request will be challenged
+    /// under isn't known, a realm-scoped entry is only returned if it's the *only* realm cached
+    /// for this origin — with more than one realm cached, guessing could attach one protected
+    /// area's credentials to a request bound for another, so [`None`] is returned instead and the
+    /// caller falls back further (e.g. to `.netrc`).
+    pub(crate) fn get(&self, url: &Url) -> Option<StoredCredentials> {
+        let scope = Scope::from_url(url)?;
+
+        if let Some(entry) = self.credentials.get(&scope) {
+            return Some(entry.value().clone());
+        }
+
+        let mut realm_scoped = self
+            .credentials
+            .iter()
+            .filter(|entry| entry.key().realm.is_some() && entry.key().same_origin(&scope));
+        let first = realm_scoped.next()?;
+        if realm_scoped.next().is_some() {
+            return None;
+        }
+        Some(first.value().clone())
+    }
+
+    /// Record `credentials` discovered from a successful challenge response to a request to
+    /// `url`, scoped to the responding origin and, if present, the challenge's `realm`.
+    pub(crate) fn insert(&self, url: &Url, realm: Option<&str>, credentials: StoredCredentials) {
+        let scope = match realm {
+            Some(realm) => Scope::from_url_and_realm(url, realm),
+            None => Scope::from_url(url),
+        };
+        if let Some(scope) = scope {
+            self.credentials.insert(scope, credentials);
+        }
+    }
+
+    /// Returns `true` if credentials realized for a request to `from` may be attached to a
+    /// redirected request to `to`.
+    ///
+    /// Refuses reuse whenever the redirect crosses to a different scheme, host, or port — the
+    /// same origin check the store itself uses to key credentials (see [`Scope::same_origin`]) —
+    /// so a redirect can't be used to exfiltrate credentials to an unrelated server, or downgrade
+    /// a request from `https` to `http` and replay them in the clear.
+    pub(crate) fn redirect_is_safe(from: &Url, to: &Url) -> bool {
+        match (Scope::from_url(from), Scope::from_url(to)) {
+            (Some(from), Some(to)) => from.same_origin(&to),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn basic(username: &str, password: &str) -> StoredCredentials {
+        StoredCredentials::Direct(Credentials::new(
+            Some(username.to_string()),
+            Some(password.to_string()),
+        ))
+    }
+
+    #[test]
+    fn insert_and_get_by_origin() {
+        let store = CredentialStore::new();
+        let url = Url::parse("https://example.com/simple/first/").unwrap();
+        store.insert(&url, None, basic("user", "password"));
+
+        let other_path = Url::parse("https://example.com/simple/second/").unwrap();
+        assert!(matches!(
+            store.get(&other_path),
+            Some(StoredCredentials::Direct(_))
+        ));
+    }
+
+    #[test]
+    fn insert_is_scoped_to_realm() {
+        let store = CredentialStore::new();
+        let url = Url::parse("https://example.com/private/").unwrap();
+        store.insert(&url, Some("private-realm"), basic("user", "password"));
+
+        assert!(store.get(&url).is_some());
+    }
+
+    #[test]
+    fn does_not_leak_across_hosts() {
+        let store = CredentialStore::new();
+        let url = Url::parse("https://example.com/simple/first/").unwrap();
+        store.insert(&url, None, basic("user", "password"));
+
+        let other_host = Url::parse("https://not-example.com/simple/first/").unwrap();
+        assert!(store.get(&other_host).is_none());
+    }
+
+    #[test]
+    fn does_not_guess_between_multiple_cached_realms() {
+        let store = CredentialStore::new();
+        let url = Url::parse("https://example.com/private/").unwrap();
+        store.insert(&url, Some("realm-a"), basic("user-a", "password-a"));
+        store.insert(&url, Some("realm-b"), basic("user-b", "password-b"));
+
+        // Neither realm is returned pre-emptively: we don't know which one a fresh,
+        // not-yet-challenged request will need.
+        assert!(store.get(&url).is_none());
+    }
+
+    #[test]
+    fn origin_wide_entry_is_unambiguous_even_with_realms_cached() {
+        let store = CredentialStore::new();
+        let url = Url::parse("https://example.com/private/").unwrap();
+        store.insert(&url, Some("realm-a"), basic("user-a", "password-a"));
+        store.insert(&url, None, basic("origin-wide", "password"));
+
+        assert!(store.get(&url).is_some());
+    }
+
+    #[test]
+    fn redirect_safety() {
+        let from = Url::parse("https://example.com/simple/first/").unwrap();
+        let same_origin = Url::parse("https://example.com/other/").unwrap();
+        let other_host = Url::parse("https://not-example.com/simple/first/").unwrap();
+        let downgraded_scheme = Url::parse("http://example.com/simple/first/").unwrap();
+        let other_port = Url::parse("https://example.com:8443/simple/first/").unwrap();
+
+        assert!(CredentialStore::redirect_is_safe(&from, &same_origin));
+        assert!(!CredentialStore::redirect_is_safe(&from, &other_host));
+        assert!(!CredentialStore::redirect_is_safe(
+            &from,
+            &downgraded_scheme
+        ));
+        assert!(!CredentialStore::redirect_is_safe(&from, &other_port));
+    }
+}